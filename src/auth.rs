@@ -0,0 +1,274 @@
+use crate::App;
+use anyhow::{anyhow, Result};
+use axum::{
+    body::Body,
+    extract::State,
+    http::{Request, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Json, Router,
+};
+use rand::RngCore;
+use secp256k1::{schnorr::Signature, Message, Secp256k1, XOnlyPublicKey};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use std::{
+    collections::HashMap,
+    sync::{Arc, RwLock},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+const CHALLENGE_BYTES: usize = 32;
+const CLOCK_SKEW_SECS: i64 = 10 * 60;
+const AUTH_EVENT_KIND: i32 = 22242;
+/// How long a bearer token from `/api/auth/verify` stays valid. Keeps the
+/// NIP-42 "short-lived token" contract instead of handing out a forever
+/// credential, and bounds `sessions`' memory growth for a long-running
+/// process.
+const SESSION_TTL_SECS: i64 = 60 * 60;
+
+/// NIP-42 state: outstanding challenges waiting to be redeemed, and bearer
+/// tokens handed out once an event proves ownership of a pubkey. Mirrors the
+/// in-memory block/allow-list pattern the rest of this crate uses for
+/// anything that needs to be consulted on every request.
+pub struct AuthState {
+    relay_url: String,
+    challenges: RwLock<HashMap<String, i64>>,
+    sessions: RwLock<HashMap<String, Session>>,
+}
+
+struct Session {
+    pubkey: String,
+    issued_at: i64,
+}
+
+impl AuthState {
+    pub fn new(relay_url: String) -> Self {
+        Self {
+            relay_url,
+            challenges: RwLock::new(HashMap::new()),
+            sessions: RwLock::new(HashMap::new()),
+        }
+    }
+
+    fn issue_challenge(&self) -> String {
+        let challenge = random_hex();
+        let mut challenges = self.challenges.write().unwrap();
+        // `/api/auth/challenge` needs no auth to call, so an unredeemed
+        // challenge an attacker never comes back for would otherwise sit in
+        // this map forever. Sweep expired ones here, same as `issue_session`
+        // does for `sessions`.
+        challenges.retain(|_, issued_at| now() - *issued_at <= CLOCK_SKEW_SECS);
+        challenges.insert(challenge.clone(), now());
+        challenge
+    }
+
+    fn take_challenge(&self, challenge: &str) -> Option<i64> {
+        let issued_at = self.challenges.write().unwrap().remove(challenge)?;
+        if now() - issued_at > CLOCK_SKEW_SECS {
+            return None;
+        }
+        Some(issued_at)
+    }
+
+    fn issue_session(&self, pubkey: String) -> String {
+        let token = random_hex();
+        let mut sessions = self.sessions.write().unwrap();
+        // Evict anything that's expired while we hold the write lock anyway,
+        // rather than running a separate sweep task for a map this small.
+        sessions.retain(|_, session| !session.is_expired());
+        sessions.insert(
+            token.clone(),
+            Session {
+                pubkey,
+                issued_at: now(),
+            },
+        );
+        token
+    }
+
+    fn pubkey_for(&self, token: &str) -> Option<String> {
+        let sessions = self.sessions.read().unwrap();
+        let session = sessions.get(token)?;
+        if session.is_expired() {
+            return None;
+        }
+        Some(session.pubkey.clone())
+    }
+}
+
+impl Session {
+    fn is_expired(&self) -> bool {
+        now() - self.issued_at > SESSION_TTL_SECS
+    }
+}
+
+fn random_hex() -> String {
+    let mut bytes = [0u8; CHALLENGE_BYTES];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    hex::encode(bytes)
+}
+
+fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs() as i64
+}
+
+#[derive(Debug, Deserialize)]
+struct AuthEvent {
+    id: String,
+    pubkey: String,
+    created_at: i64,
+    kind: i32,
+    tags: Vec<Vec<String>>,
+    content: String,
+    sig: String,
+}
+
+impl AuthEvent {
+    /// NIP-01 event id: sha256 of the serialized `[0, pubkey, created_at,
+    /// kind, tags, content]` array.
+    fn computed_id(&self) -> String {
+        let serialized = json!([0, self.pubkey, self.created_at, self.kind, self.tags, self.content]);
+        let mut hasher = Sha256::new();
+        hasher.update(serialized.to_string().as_bytes());
+        hex::encode(hasher.finalize())
+    }
+
+    fn tag(&self, key: &str) -> Option<&str> {
+        self.tags
+            .iter()
+            .find(|t| t.first().map(|k| k == key).unwrap_or(false))
+            .and_then(|t| t.get(1))
+            .map(String::as_str)
+    }
+
+    fn verify_signature(&self) -> Result<()> {
+        let secp = Secp256k1::verification_only();
+        let pubkey = XOnlyPublicKey::from_slice(&hex::decode(&self.pubkey)?)?;
+        let sig = Signature::from_slice(&hex::decode(&self.sig)?)?;
+        let msg = Message::from_digest_slice(&hex::decode(&self.id)?)?;
+        secp.verify_schnorr(&sig, &msg, &pubkey)
+            .map_err(|e| anyhow!("bad signature: {e}"))
+    }
+}
+
+struct AuthError(String);
+
+impl IntoResponse for AuthError {
+    fn into_response(self) -> Response {
+        (StatusCode::UNAUTHORIZED, self.0).into_response()
+    }
+}
+
+#[derive(Serialize)]
+struct ChallengeResponse {
+    challenge: String,
+}
+
+async fn issue_challenge(State(app): State<Arc<App>>) -> Json<ChallengeResponse> {
+    Json(ChallengeResponse {
+        challenge: app.auth.issue_challenge(),
+    })
+}
+
+#[derive(Deserialize)]
+struct VerifyRequest {
+    event: AuthEvent,
+}
+
+#[derive(Serialize)]
+struct VerifyResponse {
+    token: String,
+}
+
+async fn verify(
+    State(app): State<Arc<App>>,
+    Json(req): Json<VerifyRequest>,
+) -> Result<Json<VerifyResponse>, AuthError> {
+    let event = req.event;
+
+    if event.kind != AUTH_EVENT_KIND {
+        return Err(AuthError(format!("expected kind {AUTH_EVENT_KIND}")));
+    }
+
+    let challenge = event
+        .tag("challenge")
+        .ok_or_else(|| AuthError("missing challenge tag".into()))?
+        .to_string();
+    let issued_at = app
+        .auth
+        .take_challenge(&challenge)
+        .ok_or_else(|| AuthError("unknown or already-used challenge".into()))?;
+
+    let skew = (now() - issued_at).abs().max((now() - event.created_at).abs());
+    if skew > CLOCK_SKEW_SECS {
+        return Err(AuthError("challenge or event has expired".into()));
+    }
+
+    let relay = event
+        .tag("relay")
+        .ok_or_else(|| AuthError("missing relay tag".into()))?;
+    if relay.trim_end_matches('/') != app.auth.relay_url.trim_end_matches('/') {
+        return Err(AuthError("relay tag does not match this server".into()));
+    }
+
+    if event.computed_id() != event.id {
+        return Err(AuthError("event id does not match its contents".into()));
+    }
+
+    event
+        .verify_signature()
+        .map_err(|e| AuthError(e.to_string()))?;
+
+    let token = app.auth.issue_session(event.pubkey);
+    Ok(Json(VerifyResponse { token }))
+}
+
+/// Axum middleware gating `/api/*` behind a bearer token obtained from
+/// `/api/auth/verify`. A no-op unless `CONFIG_ENABLE_AUTH` is set.
+pub async fn require_auth(State(app): State<Arc<App>>, req: Request<Body>, next: Next) -> Response {
+    if !app.enable_auth {
+        return next.run(req).await;
+    }
+
+    let token = req
+        .headers()
+        .get("authorization")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "));
+
+    match token.and_then(|t| app.auth.pubkey_for(t)) {
+        Some(_pubkey) => next.run(req).await,
+        None => (StatusCode::UNAUTHORIZED, "auth required").into_response(),
+    }
+}
+
+/// Axum middleware gating the admin-only moderation routes behind a static
+/// bearer token read from `CONFIG_ADMIN_TOKEN`, independent of the optional
+/// NIP-42 `CONFIG_ENABLE_AUTH` toggle. An "admin-only" API that stays wide
+/// open whenever NIP-42 is off (the default) isn't admin-only at all, so
+/// this fails closed: an unset `CONFIG_ADMIN_TOKEN` rejects every request
+/// rather than letting it through.
+pub async fn require_admin(State(app): State<Arc<App>>, req: Request<Body>, next: Next) -> Response {
+    let token = req
+        .headers()
+        .get("authorization")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "));
+
+    match (app.admin_token.as_deref(), token) {
+        (Some(expected), Some(got)) if expected == got => next.run(req).await,
+        _ => (StatusCode::UNAUTHORIZED, "admin auth required").into_response(),
+    }
+}
+
+pub fn router() -> Router<Arc<App>> {
+    Router::new()
+        .route("/api/auth/challenge", get(issue_challenge))
+        .route("/api/auth/verify", post(verify))
+}
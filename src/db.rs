@@ -1,17 +1,36 @@
-use sqlx::{postgres::PgPoolOptions, PgPool, migrate::Migrator};
 use anyhow::Result;
-
-//static MIGRATOR: Migrator = sqlx::migrate!();
+use sqlx::{postgres::PgPoolOptions, PgPool};
 
 pub type Db = PgPool;
 
+static MIGRATOR: sqlx::migrate::Migrator = sqlx::migrate!();
+
+/// Default `hnsw.ef_search` applied to every pooled connection. Higher
+/// values trade latency for recall on the `<=>` ANN queries in
+/// `discovery::search`; operators can override it with `CONFIG_HNSW_EF_SEARCH`
+/// without re-indexing.
+const DEFAULT_HNSW_EF_SEARCH: u32 = 40;
+
 pub async fn initDb(connection_string: &str) -> Result<Db> {
+    let ef_search = std::env::var("CONFIG_HNSW_EF_SEARCH")
+        .ok()
+        .and_then(|v| v.parse::<u32>().ok())
+        .unwrap_or(DEFAULT_HNSW_EF_SEARCH);
+
     let pool = PgPoolOptions::new()
         .max_connections(5)
-        .connect(&connection_string)
+        .after_connect(move |conn, _meta| {
+            Box::pin(async move {
+                sqlx::query(&format!("SET hnsw.ef_search = {ef_search}"))
+                    .execute(conn)
+                    .await?;
+                Ok(())
+            })
+        })
+        .connect(connection_string)
         .await?;
 
-    //MIGRATOR.run(&pool).await?;
+    MIGRATOR.run(&pool).await?;
 
     Ok(pool)
 }
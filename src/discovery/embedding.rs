@@ -1,10 +1,143 @@
-use crate::App;
-use anyhow::Result;
+use anyhow::{anyhow, bail, Result};
+use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
 
 pub type Embedding = Vec<f32>;
 
+/// A backend that turns text into a vector comparable under pgvector.
+/// Swapping models means implementing this trait and selecting it via
+/// `CONFIG_EMBEDDING_PROVIDER`, not editing the search SQL.
+#[async_trait]
+pub trait EmbeddingProvider {
+    async fn embed(&self, text: &str) -> Result<Embedding>;
+
+    /// Embeds every string in `texts` as a single request where the backend
+    /// supports it, so a burst of inputs (e.g. `plugin::run_plugin`'s
+    /// debounce batch) costs one provider round trip instead of one per
+    /// text. Returned vectors are in the same order as `texts`. The default
+    /// falls back to one `embed` call per text for backends with no native
+    /// batch endpoint; override it for backends that have one.
+    async fn embed_batch(&self, texts: &[&str]) -> Result<Vec<Embedding>> {
+        futures::future::try_join_all(texts.iter().map(|text| self.embed(text))).await
+    }
+
+    /// Length of the vectors this provider returns. Must match the
+    /// `embedding` column's declared dimension.
+    fn dimension(&self) -> usize;
+
+    /// The pgvector operator used to order by distance for this provider
+    /// (e.g. `<=>` for cosine, `<->` for Euclidean). Must match the
+    /// operator class of `idx_events_embedding_hnsw`
+    /// (`migrations/0001_init.sql`) or ANN queries silently fall back to a
+    /// sequential scan; since that index is `vector_cosine_ops`, every
+    /// built-in provider returns `<=>` here.
+    fn distance_op(&self) -> &'static str;
+}
+
+pub mod google {
+    use super::*;
+
+    const DIMENSION: usize = 768;
+
+    pub struct GoogleProvider {
+        client: reqwest::Client,
+        api_key: String,
+    }
+
+    impl GoogleProvider {
+        pub fn new(api_key: String) -> Self {
+            Self {
+                client: reqwest::Client::new(),
+                api_key,
+            }
+        }
+    }
+
+    #[derive(Serialize)]
+    struct EmbeddingRequest<'a> {
+        model: &'static str,
+        content: Content<'a>,
+    }
+
+    #[derive(Serialize)]
+    struct Content<'a> {
+        parts: Vec<Part<'a>>,
+    }
+
+    #[derive(Serialize)]
+    struct Part<'a> {
+        text: &'a str,
+    }
+
+    #[derive(Deserialize)]
+    struct EmbeddingResponse {
+        embedding: EmbeddingValues,
+    }
+
+    #[derive(Deserialize)]
+    struct EmbeddingValues {
+        values: Embedding,
+    }
+
+    #[async_trait]
+    impl EmbeddingProvider for GoogleProvider {
+        async fn embed(&self, text: &str) -> Result<Embedding> {
+            let response = self
+                .client
+                .post(format!(
+                    "https://generativelanguage.googleapis.com/v1beta/models/text-embedding-004:embedContent?key={}",
+                    self.api_key
+                ))
+                .json(&EmbeddingRequest {
+                    model: "models/text-embedding-004",
+                    content: Content {
+                        parts: vec![Part { text }],
+                    },
+                })
+                .send()
+                .await?
+                .json::<EmbeddingResponse>()
+                .await?;
+
+            Ok(response.embedding.values)
+        }
+
+        fn dimension(&self) -> usize {
+            DIMENSION
+        }
+
+        fn distance_op(&self) -> &'static str {
+            // The only ANN index this crate provisions (see
+            // `migrations/0001_init.sql`) is `vector_cosine_ops`, so every
+            // provider standardizes on cosine distance here rather than
+            // each picking its own op and needing a matching index (and a
+            // migration) to stay off a sequential scan.
+            "<=>"
+        }
+    }
+}
+
 pub mod cloudflare {
+    use super::*;
+
+    const DIMENSION: usize = 1024;
+
+    pub struct CloudflareProvider {
+        client: reqwest::Client,
+        account_id: String,
+        api_key: String,
+    }
+
+    impl CloudflareProvider {
+        pub fn new(account_id: String, api_key: String) -> Self {
+            Self {
+                client: reqwest::Client::new(),
+                account_id,
+                api_key,
+            }
+        }
+    }
+
     #[derive(Serialize)]
     struct EmbeddingContext<'a> {
         text: &'a str,
@@ -13,7 +146,7 @@ pub mod cloudflare {
     #[derive(Serialize)]
     struct EmbeddingRequest<'a> {
         query: Option<&'a str>, // honestly we are probably never going to use this field
-        contexts: Vec<CloudflareEmbeddingContext<'a>>,
+        contexts: Vec<EmbeddingContext<'a>>,
     }
 
     #[derive(Deserialize)]
@@ -23,32 +156,126 @@ pub mod cloudflare {
 
     #[derive(Deserialize)]
     struct EmbeddingResponse {
-        result: CloudflareEmbeddingResult,
+        result: EmbeddingResult,
+        #[allow(dead_code)]
         success: bool,
+        #[allow(dead_code)]
         errors: Vec<String>,
+        #[allow(dead_code)]
         messages: Vec<String>,
     }
 
-    pub async fn generate_embedding(app: &App, text: &str) -> Result<Embedding> {
-        let response = app
-            .web_client
-            .post(format!(
-                "https://api.cloudflare.com/client/v4/accounts/{}/ai/run/@cf/baai/bge-m3",
-                app.cloudflare_account_id
-            ))
-            .header(
-                "Authorization",
-                format!("Bearer {}", app.cloudflare_api_key),
-            )
-            .json(&EmbeddingRequest {
-                query: None,
-                contexts: vec![EmbeddingContext { text: text }],
-            })
-            .send()
-            .await?
-            .json::<EmbeddingResponse>()
-            .await?;
-
-        Ok(response.result.response[0].clone())
+    #[async_trait]
+    impl EmbeddingProvider for CloudflareProvider {
+        async fn embed(&self, text: &str) -> Result<Embedding> {
+            Ok(self.embed_batch(&[text]).await?.remove(0))
+        }
+
+        async fn embed_batch(&self, texts: &[&str]) -> Result<Vec<Embedding>> {
+            // bge-m3 already accepts a `contexts` array, so N texts cost one
+            // request instead of N; this is the whole reason the field is
+            // plural.
+            let response = self
+                .client
+                .post(format!(
+                    "https://api.cloudflare.com/client/v4/accounts/{}/ai/run/@cf/baai/bge-m3",
+                    self.account_id
+                ))
+                .header("Authorization", format!("Bearer {}", self.api_key))
+                .json(&EmbeddingRequest {
+                    query: None,
+                    contexts: texts.iter().map(|text| EmbeddingContext { text }).collect(),
+                })
+                .send()
+                .await?
+                .json::<EmbeddingResponse>()
+                .await?;
+
+            Ok(response.result.response)
+        }
+
+        fn dimension(&self) -> usize {
+            DIMENSION
+        }
+
+        fn distance_op(&self) -> &'static str {
+            "<=>"
+        }
     }
 }
+
+pub mod local {
+    use super::*;
+
+    const DIMENSION: usize = 384;
+
+    /// Placeholder local backend so the crate can run without a network
+    /// embedding provider configured. Hashes tokens into a fixed-size
+    /// vector rather than running a real model; swap in an ONNX/candle
+    /// model here once one is vendored.
+    pub struct LocalProvider;
+
+    impl LocalProvider {
+        pub fn new() -> Self {
+            Self
+        }
+    }
+
+    #[async_trait]
+    impl EmbeddingProvider for LocalProvider {
+        async fn embed(&self, text: &str) -> Result<Embedding> {
+            let mut vec = vec![0f32; DIMENSION];
+            for token in text.split_whitespace() {
+                let mut hasher = std::collections::hash_map::DefaultHasher::new();
+                std::hash::Hash::hash(&token, &mut hasher);
+                let bucket = (std::hash::Hasher::finish(&hasher) as usize) % DIMENSION;
+                vec[bucket] += 1.0;
+            }
+            Ok(vec)
+        }
+
+        fn dimension(&self) -> usize {
+            DIMENSION
+        }
+
+        fn distance_op(&self) -> &'static str {
+            "<=>"
+        }
+    }
+}
+
+/// Confirms `nostr_search.events.embedding` was provisioned with the same
+/// dimension the configured provider returns. The migration bakes in a
+/// fixed column width (see `migrations/0001_init.sql`), so selecting a
+/// provider of a different dimension (e.g. `google`'s 768 or `local`'s 384
+/// against the default 1024 column) would otherwise only surface as a
+/// pgvector dimension-mismatch error on the first insert or query; this
+/// fails fast at startup instead.
+pub async fn verify_column_dimension(
+    db: &crate::db::Db,
+    provider: &(dyn EmbeddingProvider + Send + Sync),
+) -> Result<()> {
+    let column_type: String = sqlx::query_scalar(
+        "SELECT format_type(atttypid, atttypmod) FROM pg_attribute
+         WHERE attrelid = 'nostr_search.events'::regclass
+           AND attname = 'embedding' AND NOT attisdropped",
+    )
+    .fetch_one(db)
+    .await?;
+
+    let column_dimension: usize = column_type
+        .strip_prefix("vector(")
+        .and_then(|s| s.strip_suffix(')'))
+        .and_then(|s| s.parse().ok())
+        .ok_or_else(|| anyhow!("could not parse embedding column type {column_type:?}"))?;
+
+    if column_dimension != provider.dimension() {
+        bail!(
+            "configured embedding provider returns {} dims but nostr_search.events.embedding is {column_type}; \
+             resize the column with a migration or pick a provider matching that dimension",
+            provider.dimension()
+        );
+    }
+
+    Ok(())
+}
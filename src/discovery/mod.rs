@@ -0,0 +1,3 @@
+pub mod embedding;
+pub mod realtime;
+pub mod search;
@@ -0,0 +1,126 @@
+use crate::{discovery::search::{self, SearchQuery}, App};
+use anyhow::Result;
+use axum::{
+    extract::{
+        ws::{Message, WebSocket, WebSocketUpgrade},
+        State,
+    },
+    response::IntoResponse,
+};
+use sqlx::postgres::PgListener;
+use std::sync::Arc;
+use tokio::sync::broadcast;
+
+/// A row-level change observed on `nostr_search.events`, fanned out from the
+/// `new_event`/`rm_event` channels that `migrations/0003_notify_triggers.sql`
+/// wires up via `pg_notify`.
+#[derive(Debug, Clone)]
+pub enum EventNotification {
+    New(String),
+    Removed(String),
+}
+
+const NOTIFY_CHANNEL_CAPACITY: usize = 1024;
+
+pub fn notify_channel() -> broadcast::Sender<EventNotification> {
+    let (tx, _) = broadcast::channel(NOTIFY_CHANNEL_CAPACITY);
+    tx
+}
+
+/// Holds a dedicated `PgListener` open and republishes every notification
+/// onto `app.notify_tx` until `recv` returns an error, at which point the
+/// fanout task logs it and stops. Any number of
+/// `/api/subscribe` clients can fan out from the same underlying connection
+/// instead of each opening their own.
+pub async fn spawn_listener(app: Arc<App>) -> Result<()> {
+    let mut listener = PgListener::connect_with(&app.db).await?;
+    listener.listen_all(["new_event", "rm_event"]).await?;
+
+    tokio::spawn(async move {
+        loop {
+            match listener.recv().await {
+                Ok(notification) => {
+                    let payload = notification.payload().to_string();
+                    let event = match notification.channel() {
+                        "new_event" => EventNotification::New(payload),
+                        "rm_event" => EventNotification::Removed(payload),
+                        other => {
+                            tracing::warn!("unexpected notify channel: {other}");
+                            continue;
+                        }
+                    };
+                    // A send error just means nobody is subscribed right now.
+                    let _ = app.notify_tx.send(event);
+                }
+                Err(e) => {
+                    tracing::error!("pg listener error, stopping fanout: {e}");
+                    break;
+                }
+            }
+        }
+    });
+
+    Ok(())
+}
+
+/// `/api/subscribe`: upgrades to a websocket, reads a single `SearchQuery`
+/// from the client as the first text frame, then streams every matching
+/// event as it is ingested instead of making the client re-poll `/api/search`.
+pub async fn subscribe(State(app): State<Arc<App>>, ws: WebSocketUpgrade) -> impl IntoResponse {
+    ws.on_upgrade(move |socket| handle_subscription(socket, app))
+}
+
+async fn handle_subscription(mut socket: WebSocket, app: Arc<App>) {
+    let search_query: SearchQuery = match socket.recv().await {
+        Some(Ok(Message::Text(text))) => match serde_json::from_str(&text) {
+            Ok(query) => query,
+            Err(e) => {
+                let _ = socket
+                    .send(Message::Text(format!("invalid query: {e}")))
+                    .await;
+                return;
+            }
+        },
+        _ => return,
+    };
+
+    let embedding = match search::embed_subscription_query(&app, &search_query).await {
+        Ok(embedding) => embedding,
+        Err(e) => {
+            let _ = socket
+                .send(Message::Text(format!("failed to embed query: {e}")))
+                .await;
+            return;
+        }
+    };
+
+    let mut rx = app.notify_tx.subscribe();
+
+    while let Ok(notification) = rx.recv().await {
+        let id = match notification {
+            EventNotification::New(id) => id,
+            // Deletions can't be re-matched against the embedding anymore;
+            // forward the id so clients can prune it from their feed.
+            EventNotification::Removed(id) => {
+                let msg = serde_json::json!({ "removed": id });
+                if socket.send(Message::Text(msg.to_string())).await.is_err() {
+                    break;
+                }
+                continue;
+            }
+        };
+
+        match search::check_match(&app, &id, &search_query, &embedding).await {
+            Ok(Some(event)) => {
+                let Ok(payload) = serde_json::to_string(&event) else {
+                    continue;
+                };
+                if socket.send(Message::Text(payload)).await.is_err() {
+                    break;
+                }
+            }
+            Ok(None) => {}
+            Err(e) => tracing::warn!("failed to re-run similarity filter for {id}: {e}"),
+        }
+    }
+}
@@ -1,8 +1,22 @@
-use crate::{App, embedding::generate_embedding};
-use anyhow::Result;
+use crate::{moderation, App};
+use anyhow::{anyhow, Result};
+use axum::extract::{Path, Query, State};
+use axum::Json;
 use serde::{Deserialize, Serialize};
-use sqlx::{QueryBuilder, Row};
+use sqlx::{Postgres, QueryBuilder, Row};
 use pgvector::Vector;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// Constant `k` in the reciprocal rank fusion formula `1 / (k + rank)`. ~60
+/// is the value used in the original RRF paper and flattens the influence of
+/// any single list's top hit without needing per-corpus tuning.
+const RRF_K: f64 = 60.0;
+
+/// How many candidates each retriever contributes to a hybrid search before
+/// fusion. Wider than `limit`/`offset` so fusion has enough of each list's
+/// tail to re-rank against the other.
+const HYBRID_CANDIDATES_PER_LIST: i64 = 100;
 
 #[derive(Debug, Deserialize, Clone)]
 struct TagFilters {
@@ -23,6 +37,24 @@ pub struct SearchQuery {
     limit: Option<i64>,
     offset: Option<i64>,
     filters: Option<SearchFilters>,
+    /// Retrieval strategy. Defaults to `vector` so existing callers that
+    /// don't send this field keep today's pure-ANN ranking.
+    #[serde(default)]
+    mode: SearchMode,
+    /// Minimum cosine/Euclidean similarity (see `search_vector`'s
+    /// `similarity` column) a `/api/subscribe` match must clear to be
+    /// streamed to the client. Only consulted by `check_match`; a plain
+    /// `/api/search` returns its whole ranked page regardless.
+    min_similarity: Option<f64>,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SearchMode {
+    #[default]
+    Vector,
+    Keyword,
+    Hybrid,
 }
 
 #[derive(Debug, Deserialize, Clone)]
@@ -41,201 +73,592 @@ pub struct SearchResult {
 }
 
 #[derive(Debug, Serialize, sqlx::FromRow)]
-struct EventWithSimilarity {
+pub struct EventWithSimilarity {
     id: String,
     pubkey: String,
     created_at: i64,
     kind: i32,
     content: String,
     tags: serde_json::Value,
+    /// Raw retrieval score: cosine/Euclidean similarity in `vector` mode,
+    /// `ts_rank` in `keyword` mode, and cosine similarity in `hybrid` mode
+    /// (fused ordering is carried separately in `rrf_score`).
     similarity: f64,
+    /// Fused reciprocal-rank-fusion score. Only populated in `hybrid` mode.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[sqlx(default)]
+    rrf_score: Option<f64>,
 }
 
-pub async fn search_events(app: &App, search_query: SearchQuery) -> Result<SearchResult> {
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct TagValue {
+    value: String,
+    count: i64,
+}
+
+fn banned_pubkeys(app: &App) -> Vec<String> {
+    app.banned_pubkeys.read().unwrap().iter().cloned().collect()
+}
+
+/// Appends `WHERE`/`AND pubkey = ... AND kind = ... AND tags @> ...` clauses
+/// for `filters` to `qb`, tracking whether a `WHERE` has already been opened
+/// so callers building several differently-shaped queries (select, count,
+/// candidate lists) don't have to duplicate that bookkeeping.
+fn push_search_filters(
+    qb: &mut QueryBuilder<'_, Postgres>,
+    filters: &Option<SearchFilters>,
+    first_condition: &mut bool,
+) {
+    let Some(filters) = filters else { return };
+
+    if let Some(pubkey) = &filters.pubkey {
+        qb.push(if *first_condition { " WHERE " } else { " AND " });
+        qb.push("pubkey = ").push_bind(pubkey.clone());
+        *first_condition = false;
+    }
+
+    if let Some(kind) = filters.kind {
+        qb.push(if *first_condition { " WHERE " } else { " AND " });
+        qb.push("kind = ").push_bind(kind);
+        *first_condition = false;
+    }
+
+    if let Some(tag_filters) = &filters.tags {
+        if let Some(exact) = &tag_filters.exact {
+            qb.push(if *first_condition { " WHERE " } else { " AND " });
+            qb.push("tags @> ").push_bind(exact.clone());
+            *first_condition = false;
+        }
+    }
+}
+
+/// Runs the plain vector ANN retrieval: the existing pure cosine/Euclidean
+/// similarity order this crate has always used for `mode: "vector"`.
+async fn search_vector(app: &App, search_query: &SearchQuery, embedding: &Vector) -> Result<SearchResult> {
     let limit = search_query.limit.unwrap_or(10);
     let offset = search_query.offset.unwrap_or(0);
+    let distance_op = app.embedding_provider.distance_op();
+
+    let mut qb = QueryBuilder::new(format!(
+        "SELECT id, pubkey, created_at, kind, content, tags, 1 - (embedding {distance_op} "
+    ));
+    qb.push_bind(embedding.clone())
+        .push(") as similarity FROM nostr_search.events");
 
-    let embedding_vec = generate_embedding(&app, &search_query.query).await?;
+    let mut first_condition = true;
+    push_search_filters(&mut qb, &search_query.filters, &mut first_condition);
+    moderation::exclude_banned(&mut qb, banned_pubkeys(app), &mut first_condition);
 
-    let embedding = Vector::from(embedding_vec);
+    qb.push(format!(" ORDER BY embedding {distance_op} "))
+        .push_bind(embedding.clone())
+        .push(" LIMIT ")
+        .push_bind(limit)
+        .push(" OFFSET ")
+        .push_bind(offset);
+
+    let results = qb
+        .build_query_as::<EventWithSimilarity>()
+        .fetch_all(&app.db)
+        .await?;
+
+    let mut count_qb = QueryBuilder::new("SELECT COUNT(*) FROM nostr_search.events");
+    let mut first_condition = true;
+    push_search_filters(&mut count_qb, &search_query.filters, &mut first_condition);
+    moderation::exclude_banned(&mut count_qb, banned_pubkeys(app), &mut first_condition);
+
+    let total: i64 = count_qb.build().fetch_one(&app.db).await?.get(0);
+
+    Ok(SearchResult { results, total, limit, offset })
+}
+
+/// Runs the plain full-text retrieval: `websearch_to_tsquery` over `content`,
+/// ordered by `ts_rank`, used directly for `mode: "keyword"` and as one of
+/// the two candidate lists fused in `mode: "hybrid"`.
+async fn search_keyword(app: &App, search_query: &SearchQuery) -> Result<SearchResult> {
+    let limit = search_query.limit.unwrap_or(10);
+    let offset = search_query.offset.unwrap_or(0);
 
-    // Start building the base query
     let mut qb = QueryBuilder::new(
-        "SELECT id, pubkey, created_at, kind, content, tags, 1 - (embedding <=> ",
+        "SELECT id, pubkey, created_at, kind, content, tags,
+                ts_rank(to_tsvector('english', content), websearch_to_tsquery('english', ",
     );
+    qb.push_bind(search_query.query.clone())
+        .push(")) as similarity FROM nostr_search.events WHERE to_tsvector('english', content) @@ websearch_to_tsquery('english', ")
+        .push_bind(search_query.query.clone())
+        .push(")");
+
+    let mut first_condition = false;
+    push_search_filters(&mut qb, &search_query.filters, &mut first_condition);
+    moderation::exclude_banned(&mut qb, banned_pubkeys(app), &mut first_condition);
+
+    qb.push(" ORDER BY similarity DESC LIMIT ")
+        .push_bind(limit)
+        .push(" OFFSET ")
+        .push_bind(offset);
+
+    let results = qb
+        .build_query_as::<EventWithSimilarity>()
+        .fetch_all(&app.db)
+        .await?;
+
+    let mut count_qb = QueryBuilder::new(
+        "SELECT COUNT(*) FROM nostr_search.events WHERE to_tsvector('english', content) @@ websearch_to_tsquery('english', ",
+    );
+    count_qb.push_bind(search_query.query.clone()).push(")");
+
+    let mut first_condition = false;
+    push_search_filters(&mut count_qb, &search_query.filters, &mut first_condition);
+    moderation::exclude_banned(&mut count_qb, banned_pubkeys(app), &mut first_condition);
 
+    let total: i64 = count_qb.build().fetch_one(&app.db).await?.get(0);
+
+    Ok(SearchResult { results, total, limit, offset })
+}
+
+/// Ids of a retriever's top `HYBRID_CANDIDATES_PER_LIST` matches, in rank
+/// order, used as one input list to reciprocal rank fusion.
+async fn vector_candidate_ids(app: &App, search_query: &SearchQuery, embedding: &Vector) -> Result<Vec<String>> {
+    let distance_op = app.embedding_provider.distance_op();
+
+    let mut qb = QueryBuilder::new("SELECT id FROM nostr_search.events");
+    let mut first_condition = true;
+    push_search_filters(&mut qb, &search_query.filters, &mut first_condition);
+    moderation::exclude_banned(&mut qb, banned_pubkeys(app), &mut first_condition);
+
+    qb.push(format!(" ORDER BY embedding {distance_op} "))
+        .push_bind(embedding.clone())
+        .push(" LIMIT ")
+        .push_bind(HYBRID_CANDIDATES_PER_LIST);
+
+    Ok(qb
+        .build()
+        .fetch_all(&app.db)
+        .await?
+        .into_iter()
+        .map(|row| row.get::<String, _>("id"))
+        .collect())
+}
+
+/// Ids of the top `HYBRID_CANDIDATES_PER_LIST` keyword matches, in rank
+/// order; the other input list to reciprocal rank fusion.
+async fn keyword_candidate_ids(app: &App, search_query: &SearchQuery) -> Result<Vec<String>> {
+    let mut qb = QueryBuilder::new(
+        "SELECT id, ts_rank(to_tsvector('english', content), websearch_to_tsquery('english', ",
+    );
+    qb.push_bind(search_query.query.clone())
+        .push(")) as rank FROM nostr_search.events WHERE to_tsvector('english', content) @@ websearch_to_tsquery('english', ")
+        .push_bind(search_query.query.clone())
+        .push(")");
+
+    let mut first_condition = false;
+    push_search_filters(&mut qb, &search_query.filters, &mut first_condition);
+    moderation::exclude_banned(&mut qb, banned_pubkeys(app), &mut first_condition);
+
+    qb.push(" ORDER BY rank DESC LIMIT ").push_bind(HYBRID_CANDIDATES_PER_LIST);
+
+    Ok(qb
+        .build()
+        .fetch_all(&app.db)
+        .await?
+        .into_iter()
+        .map(|row| row.get::<String, _>("id"))
+        .collect())
+}
+
+/// Combines ranked id lists via reciprocal rank fusion: for every id, sum
+/// `1 / (RRF_K + rank)` (1-based) across whichever lists it appears in, then
+/// sort by that sum descending. An id absent from a list simply contributes
+/// nothing for that list rather than being penalized.
+fn reciprocal_rank_fusion(lists: &[Vec<String>]) -> Vec<(String, f64)> {
+    let mut scores: HashMap<String, f64> = HashMap::new();
+
+    for list in lists {
+        for (rank, id) in list.iter().enumerate() {
+            *scores.entry(id.clone()).or_insert(0.0) += 1.0 / (RRF_K + (rank + 1) as f64);
+        }
+    }
+
+    let mut fused: Vec<(String, f64)> = scores.into_iter().collect();
+    fused.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap());
+    fused
+}
+
+/// Fetches full rows for `ids`, each annotated with the vector similarity
+/// against `embedding` regardless of which retriever surfaced it, then
+/// orders and scores them by the already-computed `fused` ranking.
+async fn fetch_fused_page(
+    app: &App,
+    ids: &[String],
+    embedding: &Vector,
+    fused_scores: &HashMap<String, f64>,
+) -> Result<Vec<EventWithSimilarity>> {
+    if ids.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let distance_op = app.embedding_provider.distance_op();
+    let mut qb = QueryBuilder::new(format!(
+        "SELECT id, pubkey, created_at, kind, content, tags, 1 - (embedding {distance_op} "
+    ));
     qb.push_bind(embedding.clone())
-        .push(") as similarity FROM nostr_search.events");
+        .push(") as similarity FROM nostr_search.events WHERE id = ANY(")
+        .push_bind(ids.to_vec())
+        .push(")");
 
-    // Add WHERE clause if we have filters
-    if let Some(filters) = search_query.clone().filters {
-        let mut first_condition = true;
+    let mut rows = qb
+        .build_query_as::<EventWithSimilarity>()
+        .fetch_all(&app.db)
+        .await?;
+
+    rows.sort_by(|a, b| {
+        fused_scores[&b.id]
+            .partial_cmp(&fused_scores[&a.id])
+            .unwrap()
+    });
+    for row in &mut rows {
+        row.rrf_score = Some(fused_scores[&row.id]);
+    }
+
+    Ok(rows)
+}
+
+/// Runs both retrievers and fuses their rankings with reciprocal rank
+/// fusion, for `mode: "hybrid"`. `total` reflects the number of distinct ids
+/// either retriever surfaced, since a fused result set has no single "row
+/// count" query the way a plain filter does.
+async fn search_hybrid(app: &App, search_query: &SearchQuery, embedding: &Vector) -> Result<SearchResult> {
+    let limit = search_query.limit.unwrap_or(10);
+    let offset = search_query.offset.unwrap_or(0);
+
+    let (vector_ids, keyword_ids) = tokio::try_join!(
+        vector_candidate_ids(app, search_query, embedding),
+        keyword_candidate_ids(app, search_query),
+    )?;
+
+    let fused = reciprocal_rank_fusion(&[vector_ids, keyword_ids]);
+    let total = fused.len() as i64;
+    let fused_scores: HashMap<String, f64> = fused.iter().cloned().collect();
+
+    let page_ids: Vec<String> = fused
+        .into_iter()
+        .skip(offset.max(0) as usize)
+        .take(limit.max(0) as usize)
+        .map(|(id, _)| id)
+        .collect();
+
+    let results = fetch_fused_page(app, &page_ids, embedding, &fused_scores).await?;
+
+    Ok(SearchResult { results, total, limit, offset })
+}
 
+/// Embeds `text` and checks the result against `EmbeddingProvider::dimension`
+/// before it ever reaches pgvector, so a misconfigured provider fails with a
+/// clear error here instead of a cryptic dimension mismatch from Postgres.
+async fn embed_and_validate(app: &App, text: &str) -> Result<Vector> {
+    let embedding_vec = app.embedding_provider.embed(text).await?;
+    if embedding_vec.len() != app.embedding_provider.dimension() {
+        return Err(anyhow!(
+            "embedding provider returned {} dims, expected {}",
+            embedding_vec.len(),
+            app.embedding_provider.dimension()
+        ));
+    }
+    Ok(Vector::from(embedding_vec))
+}
+
+pub async fn search_events(app: &App, search_query: SearchQuery) -> Result<SearchResult> {
+    // Keyword mode needs no embedding at all, so skip the embedding call
+    // (and its dimension check) entirely for it.
+    if search_query.mode == SearchMode::Keyword {
+        return search_keyword(app, &search_query).await;
+    }
+
+    let embedding = embed_and_validate(app, &search_query.query).await?;
+
+    if search_query.mode == SearchMode::Hybrid {
+        search_hybrid(app, &search_query, &embedding).await
+    } else {
+        search_vector(app, &search_query, &embedding).await
+    }
+}
+
+/// Runs several `SearchQuery`s in one round trip, for `/api/search/batch`.
+/// Queries sharing the same `query` text (and needing an embedding at all)
+/// are embedded once via a single concurrent batch rather than once per
+/// query, since the embedding call is the most expensive part of a search.
+pub async fn search_events_batch(app: &App, queries: Vec<SearchQuery>) -> Result<Vec<SearchResult>> {
+    let mut unique_texts = Vec::new();
+    let mut seen = std::collections::HashSet::new();
+    for query in &queries {
+        if query.mode != SearchMode::Keyword && seen.insert(query.query.clone()) {
+            unique_texts.push(query.query.clone());
+        }
+    }
+
+    let embedded = futures::future::try_join_all(
+        unique_texts.iter().map(|text| embed_and_validate(app, text)),
+    )
+    .await?;
+
+    let embeddings: HashMap<String, Vector> = unique_texts.into_iter().zip(embedded).collect();
+
+    let mut results = Vec::with_capacity(queries.len());
+    for query in queries {
+        let result = match query.mode {
+            SearchMode::Keyword => search_keyword(app, &query).await?,
+            SearchMode::Hybrid => search_hybrid(app, &query, &embeddings[&query.query]).await?,
+            SearchMode::Vector => search_vector(app, &query, &embeddings[&query.query]).await?,
+        };
+        results.push(result);
+    }
+
+    Ok(results)
+}
+
+pub async fn get_similar_events(
+    State(app): State<Arc<App>>,
+    Path(event_id): Path<String>,
+    Query(params): Query<std::collections::HashMap<String, String>>,
+) -> Result<Json<Vec<EventWithSimilarity>>, String> {
+    let limit = params
+        .get("limit")
+        .and_then(|l| l.parse::<i64>().ok())
+        .unwrap_or(5);
+
+    let distance_op = app.embedding_provider.distance_op();
+
+    let mut qb = QueryBuilder::new(
+        "WITH event_embedding AS (
+            SELECT embedding FROM nostr_search.events WHERE id = ",
+    );
+    qb.push_bind(event_id.clone()).push(format!(
+        "
+        )
+        SELECT
+            ne.id, ne.pubkey, ne.created_at, ne.kind, ne.content, ne.tags,
+            1 - (ne.embedding {distance_op} e.embedding) as similarity
+        FROM nostr_search.events ne, event_embedding e
+        WHERE ne.id != "
+    ));
+    qb.push_bind(event_id);
+
+    let banned = banned_pubkeys(&app);
+    if !banned.is_empty() {
+        qb.push(" AND ne.pubkey <> ALL(").push_bind(banned).push(")");
+    }
+
+    qb.push(format!(" ORDER BY ne.embedding {distance_op} e.embedding LIMIT "))
+        .push_bind(limit);
+
+    let similar_events = qb
+        .build_query_as::<EventWithSimilarity>()
+        .fetch_all(&app.db)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    Ok(Json(similar_events))
+}
+
+pub async fn get_tag_values(
+    State(app): State<Arc<App>>,
+    Path(tag_key): Path<String>,
+    Query(params): Query<std::collections::HashMap<String, String>>,
+) -> Result<Json<Vec<TagValue>>, String> {
+    let limit = params
+        .get("limit")
+        .and_then(|l| l.parse::<i64>().ok())
+        .unwrap_or(100);
+
+    let mut qb = QueryBuilder::new(
+        "SELECT DISTINCT tag->>'value' as value, COUNT(*) as count
+         FROM nostr_search.events,
+              jsonb_array_elements(tags) tag
+         WHERE tag->>'key' = ",
+    );
+    qb.push_bind(tag_key);
+
+    let banned = banned_pubkeys(&app);
+    if !banned.is_empty() {
+        qb.push(" AND pubkey <> ALL(").push_bind(banned).push(")");
+    }
+
+    qb.push(" GROUP BY tag->>'value' ORDER BY count DESC LIMIT ")
+        .push_bind(limit);
+
+    let values = qb
+        .build_query_as::<TagValue>()
+        .fetch_all(&app.db)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    Ok(Json(values))
+}
+
+/// Embeds a subscriber's query once, up front, so `check_match` can re-run
+/// the similarity filter for every notified event without re-embedding the
+/// (unchanged) query text on each call. Public so `realtime::handle_subscription`
+/// can compute this before entering its `rx.recv()` loop.
+pub async fn embed_subscription_query(app: &App, search_query: &SearchQuery) -> Result<Vector> {
+    embed_and_validate(app, &search_query.query).await
+}
+
+/// Re-runs `search_query`'s similarity filter against a single event id,
+/// used by the `/api/subscribe` feed to decide whether a newly notified
+/// event belongs in a client's stored query. `embedding` is the subscriber's
+/// query text embedded once via `embed_subscription_query`, not re-embedded
+/// per call.
+pub async fn check_match(
+    app: &App,
+    id: &str,
+    search_query: &SearchQuery,
+    embedding: &Vector,
+) -> Result<Option<EventWithSimilarity>> {
+    let distance_op = app.embedding_provider.distance_op();
+
+    let mut qb = QueryBuilder::new(format!(
+        "SELECT id, pubkey, created_at, kind, content, tags, 1 - (embedding {distance_op} "
+    ));
+
+    qb.push_bind(embedding.clone())
+        .push(") as similarity FROM nostr_search.events WHERE id = ")
+        .push_bind(id.to_string());
+
+    if let Some(filters) = search_query.filters.clone() {
         if let Some(pubkey) = filters.pubkey {
-            qb.push(" WHERE pubkey = ");
-            qb.push_bind(pubkey);
-            first_condition = false;
+            qb.push(" AND pubkey = ").push_bind(pubkey);
         }
 
         if let Some(kind) = filters.kind {
-            if first_condition {
-                qb.push(" WHERE ");
-            } else {
-                qb.push(" AND ");
-            }
-            qb.push("kind = ");
-            qb.push_bind(kind);
-            first_condition = false;
+            qb.push(" AND kind = ").push_bind(kind);
         }
 
         if let Some(tag_filters) = filters.tags {
             if let Some(exact) = tag_filters.exact {
-                if first_condition {
-                    qb.push(" WHERE ");
-                } else {
-                    qb.push(" AND ");
-                }
-                qb.push("tags @> ");
-                qb.push_bind(exact);
+                qb.push(" AND tags @> ").push_bind(exact);
             }
         }
     }
 
-    // Add ordering, limit and offset
-    qb.push(" ORDER BY embedding <=> ")
-        .push_bind(embedding)
-        .push(" LIMIT ")
-        .push_bind(limit)
-        .push(" OFFSET ")
-        .push_bind(offset);
+    let mut first_condition = false;
+    moderation::exclude_banned(&mut qb, banned_pubkeys(app), &mut first_condition);
+
+    if let Some(min_similarity) = search_query.min_similarity {
+        qb.push(" AND 1 - (embedding ")
+            .push(distance_op)
+            .push(" ")
+            .push_bind(embedding.clone())
+            .push(") >= ")
+            .push_bind(min_similarity);
+    }
 
-    // Build and execute the query
     let query = qb.build_query_as::<EventWithSimilarity>();
 
-    let results = query.fetch_all(&app.db).await?;
+    Ok(query.fetch_optional(&app.db).await?)
+}
 
-    // Build the count query
-    let mut count_qb = QueryBuilder::new("SELECT COUNT(*) FROM nostr_search.events");
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    if let Some(filters) = search_query.filters {
+    #[test]
+    fn rrf_fuses_lists_and_rewards_agreement() {
+        let vector_ids = vec!["a".to_string(), "b".to_string(), "c".to_string()];
+        let keyword_ids = vec!["b".to_string(), "a".to_string(), "d".to_string()];
+
+        let fused = reciprocal_rank_fusion(&[vector_ids, keyword_ids]);
+        let order: Vec<&str> = fused.iter().map(|(id, _)| id.as_str()).collect();
+
+        // "a" and "b" each appear in both lists near the top, so they should
+        // outrank "c"/"d", which only ever appear in one list.
+        assert!(order[..2].contains(&"a"));
+        assert!(order[..2].contains(&"b"));
+        assert!(!order[..2].contains(&"c"));
+        assert!(!order[..2].contains(&"d"));
+    }
+
+    #[test]
+    fn rrf_is_empty_for_no_lists() {
+        assert!(reciprocal_rank_fusion(&[]).is_empty());
+    }
+
+    #[test]
+    fn push_search_filters_opens_where_once_then_ands() {
+        let filters = Some(SearchFilters {
+            pubkey: Some("abc".to_string()),
+            kind: Some(1),
+            tags: None,
+        });
+
+        let mut qb = QueryBuilder::new("SELECT id FROM nostr_search.events");
         let mut first_condition = true;
+        push_search_filters(&mut qb, &filters, &mut first_condition);
 
-        if let Some(pubkey) = filters.pubkey {
-            count_qb.push(" WHERE pubkey = ");
-            count_qb.push_bind(pubkey);
-            first_condition = false;
-        }
+        assert!(!first_condition);
+        let sql = qb.sql();
+        assert_eq!(sql.matches("WHERE").count(), 1);
+        assert_eq!(sql.matches("AND").count(), 1);
+        assert!(sql.contains("pubkey = "));
+        assert!(sql.contains("kind = "));
+    }
 
-        if let Some(kind) = filters.kind {
-            if first_condition {
-                count_qb.push(" WHERE ");
-            } else {
-                count_qb.push(" AND ");
-            }
-            count_qb.push("kind = ");
-            count_qb.push_bind(kind);
-            first_condition = false;
-        }
+    #[test]
+    fn push_search_filters_then_exclude_banned_ands_not_wheres() {
+        let filters = Some(SearchFilters {
+            pubkey: Some("abc".to_string()),
+            kind: None,
+            tags: None,
+        });
 
-        if let Some(tag_filters) = filters.tags {
-            if let Some(exact) = tag_filters.exact {
-                if first_condition {
-                    count_qb.push(" WHERE ");
-                } else {
-                    count_qb.push(" AND ");
-                }
-                count_qb.push("tags @> ");
-                count_qb.push_bind(exact);
-            }
-        }
+        let mut qb = QueryBuilder::new("SELECT id FROM nostr_search.events");
+        let mut first_condition = true;
+        push_search_filters(&mut qb, &filters, &mut first_condition);
+        moderation::exclude_banned(&mut qb, vec!["banned".to_string()], &mut first_condition);
+
+        let sql = qb.sql();
+        assert_eq!(sql.matches("WHERE").count(), 1);
+        assert_eq!(sql.matches("AND").count(), 1);
+        assert!(sql.contains("pubkey <> ALL("));
     }
 
-    let total: i64 = count_qb
-        .build()
-        .fetch_one(&app.db)
-        .await?
-        .get(0);
-
-    Ok(SearchResult {
-        results,
-        total,
-        limit,
-        offset,
-    })
-}
-
-//pub async fn get_similar_events(
-//    State(state): State<Arc<App>>,
-//    Path(event_id): Path<String>,
-//    Query(params): Query<std::collections::HashMap<String, String>>,
-//) -> Result<Json<Vec<EventWithSimilarity>>, String> {
-//    let limit = params
-//        .get("limit")
-//        .and_then(|l| l.parse::<i64>().ok())
-//        .unwrap_or(5);
-//
-//    let query = sqlx::query_as::<_, EventWithSimilarity>(
-//        "WITH event_embedding AS (
-//            SELECT embedding
-//            FROM nostr_search.events
-//            WHERE id = $1
-//        )
-//        SELECT
-//            ne.id,
-//            ne.pubkey,
-//            ne.created_at,
-//            ne.kind,
-//            ne.content,
-//            ne.tags,
-//            1 - (ne.embedding <=> e.embedding) as similarity
-//        FROM nostr_search.events ne, event_embedding e
-//        WHERE ne.id != $1
-//        ORDER BY ne.embedding <=> e.embedding
-//        LIMIT $2",
-//    )
-//    .bind(event_id)
-//    .bind(limit);
-//
-//    let similar_events = query
-//        .fetch_all(&state.pool)
-//        .await
-//        .map_err(|e| e.to_string())?;
-//
-//    Ok(Json(similar_events))
-//}
-//
-//pub async fn get_tag_values(
-//    State(state): State<Arc<App>>,
-//    Path(tag_key): Path<String>,
-//    Query(params): Query<std::collections::HashMap<String, String>>,
-//) -> Result<Json<Vec<TagValue>>, String> {
-//    let limit = params
-//        .get("limit")
-//        .and_then(|l| l.parse::<i64>().ok())
-//        .unwrap_or(100);
-//
-//    let query = sqlx::query_as::<_, TagValue>(
-//        "SELECT DISTINCT tag->>'value' as value, COUNT(*) as count
-//         FROM nostr_search.events,
-//              jsonb_array_elements(tags) tag
-//         WHERE tag->>'key' = $1
-//         GROUP BY tag->>'value'
-//         ORDER BY count DESC
-//         LIMIT $2",
-//    )
-//    .bind(tag_key)
-//    .bind(limit);
-//
-//    let values = query
-//        .fetch_all(&state.pool)
-//        .await
-//        .map_err(|e| e.to_string())?;
-//
-//    Ok(Json(values))
-//}
-//
-//#[derive(Debug, Serialize, sqlx::FromRow)]
-//struct TagValue {
-//    value: String,
-//    count: i64,
-//}
+    #[test]
+    fn exclude_banned_opens_where_when_no_filters_applied() {
+        let mut qb = QueryBuilder::new("SELECT id FROM nostr_search.events");
+        let mut first_condition = true;
+        push_search_filters(&mut qb, &None, &mut first_condition);
+        moderation::exclude_banned(&mut qb, vec!["banned".to_string()], &mut first_condition);
+
+        assert!(!first_condition);
+        assert_eq!(qb.sql().matches("WHERE").count(), 1);
+    }
+
+    #[test]
+    fn exclude_banned_is_a_noop_for_an_empty_ban_list() {
+        let mut qb = QueryBuilder::new("SELECT id FROM nostr_search.events");
+        let mut first_condition = true;
+        moderation::exclude_banned(&mut qb, Vec::new(), &mut first_condition);
+
+        assert!(first_condition);
+        assert!(!qb.sql().contains("WHERE"));
+    }
+
+    /// Confirms the ANN ordering in `search_vector` actually hits
+    /// `idx_events_embedding_hnsw` rather than falling back to a sequential
+    /// scan, which would silently defeat the point of migration 0001.
+    #[sqlx::test(migrations = "migrations")]
+    async fn vector_order_by_uses_the_hnsw_index(pool: sqlx::PgPool) {
+        let zero = Vector::from(vec![0f32; 1024]);
+        let plan: String = sqlx::query_scalar(
+            "EXPLAIN SELECT id FROM nostr_search.events ORDER BY embedding <=> $1 LIMIT 10",
+        )
+        .bind(zero)
+        .fetch_all(&pool)
+        .await
+        .unwrap()
+        .join("\n");
+
+        assert!(
+            plan.contains("idx_events_embedding_hnsw"),
+            "expected the HNSW index in the plan, got:\n{plan}"
+        );
+    }
+}
@@ -6,34 +6,32 @@ use axum::{
     routing::{post, get},
     Json, Router,
 };
-use serde::{Deserialize, Serialize};
-use sqlx::migrate::Migrator;
-use sqlx::{postgres::PgPoolOptions, PgPool};
 use std::sync::Arc;
 
+pub mod auth;
 pub mod db;
 #[cfg(feature = "discovery")]
 pub mod discovery;
-
-// Types
-#[derive(Debug, Serialize, Deserialize)]
-struct NostrEvent {
-    id: String,
-    pubkey: String,
-    created_at: i64,
-    kind: i32,
-    content: String,
-    tags: serde_json::Value,
-}
+pub mod moderation;
+pub mod plugin;
 
 // App state
 #[derive(Clone)]
 pub struct App {
-    //pub db: db::Db,
+    pub db: db::Db,
     pub web_client: reqwest::Client,
-    pub cloudflare_account_id: Option<String>,
-    // is this a bad idea? ;)
-    pub cloudflare_api_key: Option<String>,
+    #[cfg(feature = "discovery")]
+    pub notify_tx: tokio::sync::broadcast::Sender<discovery::realtime::EventNotification>,
+    #[cfg(feature = "discovery")]
+    pub embedding_provider: Arc<dyn discovery::embedding::EmbeddingProvider + Send + Sync>,
+    pub enable_auth: bool,
+    pub auth: Arc<auth::AuthState>,
+    pub banned_pubkeys: moderation::BanList,
+    /// Bearer token gating `/api/admin/*` (see `auth::require_admin`). Unset
+    /// by default, which means the admin API is unreachable until an
+    /// operator opts in by setting `CONFIG_ADMIN_TOKEN` — not tied to
+    /// `CONFIG_ENABLE_AUTH`.
+    pub admin_token: Option<String>,
 }
 
 use clap::{Parser, Subcommand};
@@ -49,53 +47,57 @@ struct Cli {
 enum Commands {
     /// Run the daemon
     Daemon,
+    /// Run as a strfry write-policy plugin, reading events from stdin
+    Plugin,
 }
 
-#[derive(Debug, Deserialize)]
-struct PluginInput {
-    #[serde(rename = "type")]
-    msg_type: String,
-    event: NostrEvent,
-    receivedAt: i64,
-    sourceType: String,
-    sourceInfo: String,
-}
-
-#[derive(Debug, Serialize)]
-struct PluginOutput {
-    id: String,
-    action: String,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    msg: Option<String>,
-}
-
-use std::io::{self, BufRead, Write};
-
 #[tokio::main]
 async fn main() -> Result<()> {
     // Initialize tracing for logging
     tracing_subscriber::fmt::init();
 
-    //let pool = db::initDb(&std::env::var("POSTGRES_CONNECTION")?).await?;
-
-    let cf_acc_id = match std::env::var("CLOUDFLARE_ACCOUNT_ID") {
-        Ok(v) => Some(v),
-        Err(e) => None,
-    };
-
-    let cf_api_key = match std::env::var("CLOUDFLARE_API_KEY") {
-        Ok(v) => Some(v),
-        Err(e) => None,
-    };
+    let pool = db::initDb(&std::env::var("POSTGRES_CONNECTION")?).await?;
+
+    let enable_auth = std::env::var("CONFIG_ENABLE_AUTH")
+        .map(|v| v == "1" || v.eq_ignore_ascii_case("true"))
+        .unwrap_or(false);
+    let relay_url = std::env::var("CONFIG_RELAY_URL").unwrap_or_default();
+    let banned_pubkeys = moderation::load_banned_pubkeys(&pool).await?;
+    let admin_token = std::env::var("CONFIG_ADMIN_TOKEN").ok();
+
+    #[cfg(feature = "discovery")]
+    let embedding_provider: Arc<dyn discovery::embedding::EmbeddingProvider + Send + Sync> =
+        match std::env::var("CONFIG_EMBEDDING_PROVIDER").as_deref() {
+            Ok("google") => Arc::new(discovery::embedding::google::GoogleProvider::new(
+                std::env::var("GOOGLE_API_KEY")?,
+            )),
+            Ok("local") => Arc::new(discovery::embedding::local::LocalProvider::new()),
+            _ => Arc::new(discovery::embedding::cloudflare::CloudflareProvider::new(
+                std::env::var("CLOUDFLARE_ACCOUNT_ID").unwrap_or_default(),
+                std::env::var("CLOUDFLARE_API_KEY").unwrap_or_default(),
+            )),
+        };
+
+    #[cfg(feature = "discovery")]
+    discovery::embedding::verify_column_dimension(&pool, embedding_provider.as_ref()).await?;
 
     // Initialize state
     let state = Arc::new(App {
-        //db: pool,
+        db: pool,
         web_client: reqwest::Client::new(),
-        cloudflare_account_id: cf_acc_id,
-        cloudflare_api_key: cf_api_key,
+        #[cfg(feature = "discovery")]
+        notify_tx: discovery::realtime::notify_channel(),
+        #[cfg(feature = "discovery")]
+        embedding_provider,
+        enable_auth,
+        auth: Arc::new(auth::AuthState::new(relay_url)),
+        banned_pubkeys,
+        admin_token,
     });
 
+    #[cfg(feature = "discovery")]
+    discovery::realtime::spawn_listener(state.clone()).await?;
+
     let cli = Cli::parse();
 
     match cli.command {
@@ -103,6 +105,9 @@ async fn main() -> Result<()> {
             println!("Starting daemon");
             run_webserver(state).await;
         }
+        Commands::Plugin => {
+            plugin::run_plugin(state).await?;
+        }
     }
 
     Ok(())
@@ -118,13 +123,40 @@ async fn run_webserver(state: Arc<App>) -> Result<()> {
     println!("Welcome to noah");
     println!("from Chakany Systems");
     // Create router
+    let admin = moderation::router().route_layer(axum::middleware::from_fn_with_state(
+        state.clone(),
+        auth::require_admin,
+    ));
+
     let app = Router::new()
-        .route("/", get(home_path));
-        //.route("/api/events/:event_id/similar", get(get_similar_events))
-        //.route("/api/tags/:tag_key/values", get(get_tag_values))
+        .route("/", get(home_path))
+        .merge(auth::router())
+        .merge(admin);
+
+    #[cfg(feature = "discovery")]
+    let app = {
+        // These run embedding+ANN queries, so gate them behind NIP-42 auth
+        // when CONFIG_ENABLE_AUTH is on.
+        let gated = Router::new()
+            .route("/api/search", post(api_search_events))
+            .route("/api/search/batch", post(api_search_events_batch))
+            .route(
+                "/api/events/:event_id/similar",
+                get(discovery::search::get_similar_events),
+            )
+            .route(
+                "/api/tags/:tag_key/values",
+                get(discovery::search::get_tag_values),
+            )
+            .route("/api/subscribe", get(discovery::realtime::subscribe))
+            .route_layer(axum::middleware::from_fn_with_state(
+                state.clone(),
+                auth::require_auth,
+            ));
+
+        app.merge(gated)
+    };
 
-    #[cfg(feature = "search")]
-    let app = app.route("/api/search", post(api_search_events));
     let app = app.with_state(state);
 
     // Start server
@@ -156,10 +188,20 @@ where
     }
 }
 
-#[cfg(feature = "search")]
+#[cfg(feature = "discovery")]
 async fn api_search_events(
     State(state): State<Arc<App>>,
-    Json(search_query): Json<queries::SearchQuery>,
-) -> Result<Json<queries::SearchResult>, AppError> {
-    Ok(Json(queries::search_events(&state, search_query).await?))
+    Json(search_query): Json<discovery::search::SearchQuery>,
+) -> Result<Json<discovery::search::SearchResult>, AppError> {
+    Ok(Json(discovery::search::search_events(&state, search_query).await?))
+}
+
+#[cfg(feature = "discovery")]
+async fn api_search_events_batch(
+    State(state): State<Arc<App>>,
+    Json(search_queries): Json<Vec<discovery::search::SearchQuery>>,
+) -> Result<Json<Vec<discovery::search::SearchResult>>, AppError> {
+    Ok(Json(
+        discovery::search::search_events_batch(&state, search_queries).await?,
+    ))
 }
@@ -0,0 +1,114 @@
+use crate::App;
+use anyhow::Result;
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::{delete, post},
+    Router,
+};
+use sqlx::Row;
+use std::{
+    collections::HashSet,
+    sync::{Arc, RwLock},
+};
+
+/// In-memory mirror of the `banned_pubkeys` table, consulted on every
+/// search/ingest path so a ban takes effect without a database round trip.
+/// Refreshed whenever an operator adds or removes an entry.
+pub type BanList = Arc<RwLock<HashSet<String>>>;
+
+pub async fn load_banned_pubkeys(db: &crate::db::Db) -> Result<BanList> {
+    let rows = sqlx::query("SELECT pubkey FROM banned_pubkeys")
+        .fetch_all(db)
+        .await?;
+
+    let pubkeys = rows
+        .into_iter()
+        .map(|row| row.get::<String, _>("pubkey"))
+        .collect();
+
+    Ok(Arc::new(RwLock::new(pubkeys)))
+}
+
+pub fn is_banned(app: &App, pubkey: &str) -> bool {
+    app.banned_pubkeys.read().unwrap().contains(pubkey)
+}
+
+/// Appends `pubkey <> ALL(<banned>)` to a `QueryBuilder` that already has at
+/// least a `FROM` clause, prefixed with ` WHERE ` if `first_condition` is
+/// still set (clearing it) and with ` AND ` otherwise. Pushes nothing when
+/// `banned` is empty.
+pub fn exclude_banned<'a>(
+    qb: &mut sqlx::QueryBuilder<'a, sqlx::Postgres>,
+    banned: Vec<String>,
+    first_condition: &mut bool,
+) {
+    if banned.is_empty() {
+        return;
+    }
+
+    if *first_condition {
+        qb.push(" WHERE ");
+        *first_condition = false;
+    } else {
+        qb.push(" AND ");
+    }
+
+    qb.push("pubkey <> ALL(").push_bind(banned).push(")");
+}
+
+async fn ban_pubkey(
+    State(app): State<Arc<App>>,
+    Path(pubkey): Path<String>,
+) -> Result<StatusCode, ModerationError> {
+    sqlx::query("INSERT INTO banned_pubkeys (pubkey) VALUES ($1) ON CONFLICT DO NOTHING")
+        .bind(&pubkey)
+        .execute(&app.db)
+        .await?;
+
+    app.banned_pubkeys.write().unwrap().insert(pubkey);
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn unban_pubkey(
+    State(app): State<Arc<App>>,
+    Path(pubkey): Path<String>,
+) -> Result<StatusCode, ModerationError> {
+    sqlx::query("DELETE FROM banned_pubkeys WHERE pubkey = $1")
+        .bind(&pubkey)
+        .execute(&app.db)
+        .await?;
+
+    app.banned_pubkeys.write().unwrap().remove(&pubkey);
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+struct ModerationError(anyhow::Error);
+
+impl IntoResponse for ModerationError {
+    fn into_response(self) -> Response {
+        (StatusCode::INTERNAL_SERVER_ERROR, self.0.to_string()).into_response()
+    }
+}
+
+impl<E> From<E> for ModerationError
+where
+    E: Into<anyhow::Error>,
+{
+    fn from(err: E) -> Self {
+        Self(err.into())
+    }
+}
+
+/// Admin-only routes. Mounted behind `auth::require_admin` (a separate
+/// `CONFIG_ADMIN_TOKEN` bearer check, not the optional NIP-42
+/// `CONFIG_ENABLE_AUTH` toggle), plus an operator's own reverse-proxy/IP
+/// allowlist, same as any other admin API.
+pub fn router() -> Router<Arc<App>> {
+    Router::new()
+        .route("/api/admin/banned/:pubkey", post(ban_pubkey))
+        .route("/api/admin/banned/:pubkey", delete(unban_pubkey))
+}
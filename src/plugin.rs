@@ -0,0 +1,244 @@
+use crate::{moderation, App};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::time::{timeout, Duration};
+
+/// Largest `content` we'll bother embedding. strfry enforces its own size
+/// limits upstream, but this keeps a misbehaving writer from blocking the
+/// plugin loop on a multi-megabyte embedding request.
+const MAX_CONTENT_BYTES: usize = 8192;
+
+/// How long to wait for another already-in-flight line to show up before
+/// giving up and embedding whatever's buffered so far. Long enough to
+/// coalesce a burst strfry hands us back-to-back, short enough that a lone
+/// event isn't held up waiting for company.
+const BATCH_DEBOUNCE: Duration = Duration::from_millis(10);
+
+#[derive(Debug, Deserialize)]
+struct NostrEvent {
+    id: String,
+    pubkey: String,
+    created_at: i64,
+    kind: i32,
+    content: String,
+    tags: serde_json::Value,
+}
+
+#[derive(Debug, Deserialize)]
+struct PluginInput {
+    #[serde(rename = "type")]
+    msg_type: String,
+    event: NostrEvent,
+    #[allow(dead_code)]
+    receivedAt: i64,
+    #[allow(dead_code)]
+    sourceType: String,
+    #[allow(dead_code)]
+    sourceInfo: String,
+}
+
+#[derive(Debug, Serialize)]
+struct PluginOutput {
+    id: String,
+    action: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    msg: Option<String>,
+}
+
+impl PluginOutput {
+    fn accept(id: String) -> Self {
+        Self { id, action: "accept", msg: None }
+    }
+
+    fn reject(id: String, msg: impl Into<String>) -> Self {
+        Self { id, action: "reject", msg: Some(msg.into()) }
+    }
+
+    fn shadow_reject(id: String, msg: impl Into<String>) -> Self {
+        Self { id, action: "shadowReject", msg: Some(msg.into()) }
+    }
+}
+
+/// Runs noah as a strfry write-policy plugin: reads newline-delimited
+/// `PluginInput` from stdin and writes a `PluginOutput` decision for each
+/// one back to stdout, indexing accepted events as they arrive instead of
+/// requiring a separate ingestion pipeline.
+pub async fn run_plugin(app: Arc<App>) -> anyhow::Result<()> {
+    let mut lines = BufReader::new(tokio::io::stdin()).lines();
+    let mut stdout = tokio::io::stdout();
+
+    while let Some(first) = lines.next_line().await? {
+        let mut batch = vec![first];
+
+        // Drain whatever else shows up within BATCH_DEBOUNCE so a burst of
+        // events shares one batched embedding request instead of paying a
+        // separate provider round trip per event.
+        while let Ok(Some(line)) = timeout(BATCH_DEBOUNCE, lines.next_line()).await {
+            batch.push(line);
+        }
+
+        let outputs = handle_batch(&app, &batch).await;
+
+        for output in outputs {
+            let mut line = serde_json::to_string(&output)?;
+            line.push('\n');
+            stdout.write_all(line.as_bytes()).await?;
+        }
+        stdout.flush().await?;
+    }
+
+    Ok(())
+}
+
+/// A line from the batch that passed every check that doesn't need an
+/// embedding, and is waiting on one before it can be indexed.
+#[cfg(feature = "discovery")]
+struct PendingEmbed {
+    event: NostrEvent,
+}
+
+enum LineOutcome {
+    /// Already has a final verdict; nothing to embed.
+    Done(PluginOutput),
+    #[cfg(feature = "discovery")]
+    NeedsEmbedding(PendingEmbed),
+}
+
+/// Decides every line's fate up front, then sends the ones that passed
+/// their non-embedding checks through a single batched `embed_batch` call
+/// rather than one `embed` call per event, so a burst of N events costs one
+/// provider round trip instead of N.
+async fn handle_batch(app: &App, batch: &[String]) -> Vec<PluginOutput> {
+    let outcomes: Vec<LineOutcome> = batch
+        .iter()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| classify_input(app, line))
+        .collect();
+
+    #[cfg(not(feature = "discovery"))]
+    {
+        outcomes
+            .into_iter()
+            .map(|outcome| match outcome {
+                LineOutcome::Done(output) => output,
+            })
+            .collect()
+    }
+
+    #[cfg(feature = "discovery")]
+    {
+        let texts: Vec<&str> = outcomes
+            .iter()
+            .filter_map(|outcome| match outcome {
+                LineOutcome::NeedsEmbedding(pending) => Some(pending.event.content.as_str()),
+                LineOutcome::Done(_) => None,
+            })
+            .collect();
+
+        let embeddings = if texts.is_empty() {
+            Ok(Vec::new())
+        } else {
+            app.embedding_provider.embed_batch(&texts).await
+        };
+
+        let mut embeddings = match embeddings {
+            Ok(embeddings) => embeddings.into_iter(),
+            // The batch call covers every pending event in one request, so a
+            // failure is shared. An embedding-provider outage is not a
+            // reason to reject: strfry treats reject/shadowReject as "drop
+            // this event", so failing closed here would turn a transient
+            // provider hiccup into permanent, relay-wide data loss. Accept
+            // the events un-indexed instead; they just won't be searchable
+            // until a future write for the same id succeeds.
+            Err(e) => {
+                tracing::warn!("embed_batch failed for {} pending event(s): {e}", texts.len());
+                return outcomes
+                    .into_iter()
+                    .map(|outcome| match outcome {
+                        LineOutcome::Done(output) => output,
+                        LineOutcome::NeedsEmbedding(pending) => PluginOutput::accept(pending.event.id),
+                    })
+                    .collect();
+            }
+        };
+
+        let mut outputs = Vec::with_capacity(outcomes.len());
+        for outcome in outcomes {
+            let output = match outcome {
+                LineOutcome::Done(output) => output,
+                LineOutcome::NeedsEmbedding(pending) => {
+                    let embedding = embeddings.next().expect("one embedding per pending event");
+                    match upsert_event(app, &pending.event, embedding).await {
+                        Ok(()) => PluginOutput::accept(pending.event.id),
+                        Err(e) => PluginOutput::reject(pending.event.id, format!("index failed: {e}")),
+                    }
+                }
+            };
+            outputs.push(output);
+        }
+        outputs
+    }
+}
+
+fn classify_input(app: &App, line: &str) -> LineOutcome {
+    let input: PluginInput = match serde_json::from_str(line) {
+        Ok(input) => input,
+        Err(e) => {
+            return LineOutcome::Done(PluginOutput::reject(
+                String::new(),
+                format!("malformed input: {e}"),
+            ))
+        }
+    };
+
+    if input.msg_type != "new" {
+        return LineOutcome::Done(PluginOutput::accept(input.event.id));
+    }
+
+    if input.event.content.len() > MAX_CONTENT_BYTES {
+        return LineOutcome::Done(PluginOutput::reject(input.event.id, "content too large to index"));
+    }
+
+    if moderation::is_banned(app, &input.event.pubkey) {
+        return LineOutcome::Done(PluginOutput::shadow_reject(input.event.id, "pubkey is banned"));
+    }
+
+    #[cfg(not(feature = "discovery"))]
+    {
+        LineOutcome::Done(PluginOutput::accept(input.event.id))
+    }
+
+    #[cfg(feature = "discovery")]
+    {
+        LineOutcome::NeedsEmbedding(PendingEmbed { event: input.event })
+    }
+}
+
+#[cfg(feature = "discovery")]
+async fn upsert_event(app: &App, event: &NostrEvent, embedding: Vec<f32>) -> anyhow::Result<()> {
+    let embedding = pgvector::Vector::from(embedding);
+
+    sqlx::query(
+        "INSERT INTO nostr_search.events (id, pubkey, created_at, kind, content, tags, embedding)
+         VALUES ($1, $2, $3, $4, $5, $6, $7)
+         ON CONFLICT (id) DO UPDATE SET
+            pubkey = EXCLUDED.pubkey,
+            created_at = EXCLUDED.created_at,
+            kind = EXCLUDED.kind,
+            content = EXCLUDED.content,
+            tags = EXCLUDED.tags,
+            embedding = EXCLUDED.embedding",
+    )
+    .bind(&event.id)
+    .bind(&event.pubkey)
+    .bind(event.created_at)
+    .bind(event.kind)
+    .bind(&event.content)
+    .bind(&event.tags)
+    .bind(embedding)
+    .execute(&app.db)
+    .await?;
+
+    Ok(())
+}